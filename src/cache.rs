@@ -0,0 +1,61 @@
+//! Content-addressed cache for `url`/`assuo-url` sources, so repeat runs reuse the bytes last
+//! fetched for a URL instead of re-fetching them. A hit is served without contacting the server,
+//! so a change to the remote data isn't picked up until the cache is bypassed (`--no-cache`).
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static NO_CACHE: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether the cache should be bypassed entirely, driven by the `--no-cache` CLI flag.
+pub(crate) fn set_no_cache(no_cache: bool) {
+    NO_CACHE.store(no_cache, Ordering::Relaxed);
+}
+
+/// Whether the cache should be consulted/written to.
+pub(crate) fn enabled() -> bool {
+    !NO_CACHE.load(Ordering::Relaxed)
+}
+
+/// The cache root: `$ASSUO_CACHE_DIR` if set, otherwise `~/.cache/assuo`.
+fn cache_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("ASSUO_CACHE_DIR") {
+        return PathBuf::from(dir);
+    }
+
+    let home = std::env::var("HOME").expect("couldn't find $HOME to place the assuo cache in");
+    PathBuf::from(home).join(".cache").join("assuo")
+}
+
+fn content_path(hash: &str) -> PathBuf {
+    cache_dir().join(hash)
+}
+
+fn index_path(key: &str) -> PathBuf {
+    cache_dir().join("index").join(key)
+}
+
+/// Builds the cache key for a `url`/`assuo-url` fetch: the URL plus every flag that affects what
+/// bytes end up on disk, so flipping a flag can't serve up a stale hit from before.
+pub(crate) fn index_key(url: &str, decompress: bool) -> String {
+    blake3::hash(format!("{}:{}", url, decompress).as_bytes())
+        .to_hex()
+        .to_string()
+}
+
+/// Looks up the last-known content hash for `key`, and returns its cached bytes if present.
+pub(crate) async fn get(key: &str) -> Option<Vec<u8>> {
+    let hash = tokio::fs::read_to_string(index_path(key)).await.ok()?;
+    tokio::fs::read(content_path(hash.trim())).await.ok()
+}
+
+/// Stores `bytes` under their content hash, and remembers that `key` last resolved to it.
+pub(crate) async fn put(key: &str, bytes: &[u8]) -> std::io::Result<()> {
+    let hash = blake3::hash(bytes).to_hex().to_string();
+
+    tokio::fs::create_dir_all(cache_dir()).await?;
+    tokio::fs::write(content_path(&hash), bytes).await?;
+
+    tokio::fs::create_dir_all(cache_dir().join("index")).await?;
+    tokio::fs::write(index_path(key), hash).await
+}
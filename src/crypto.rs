@@ -0,0 +1,31 @@
+//! Decryption for `encrypted` sources.
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+use crate::error::AssuoError;
+
+/// Decrypts `ciphertext` (its trailing 16 bytes being the Poly1305 authentication tag) with
+/// ChaCha20-Poly1305, given a 256-bit `key` and a 96-bit `nonce`. Errors out loudly -- rather
+/// than returning unauthenticated plaintext -- if the tag doesn't check out.
+pub(crate) fn decrypt(key: &[u8], nonce: &[u8], ciphertext: Vec<u8>) -> Result<Vec<u8>, AssuoError> {
+    if key.len() != 32 {
+        return Err(AssuoError::Crypto(format!(
+            "key must be 32 bytes, got {}",
+            key.len()
+        )));
+    }
+
+    if nonce.len() != 12 {
+        return Err(AssuoError::Crypto(format!(
+            "nonce must be 12 bytes, got {}",
+            nonce.len()
+        )));
+    }
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext.as_ref())
+        .map_err(|_| AssuoError::Crypto("authentication tag didn't match".to_string()))
+}
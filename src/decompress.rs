@@ -0,0 +1,36 @@
+//! Transparent decompression of fetched/on-disk bytes, auto-detected from their leading magic
+//! bytes.
+
+use std::io::Read;
+
+use crate::error::AssuoError;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: [u8; 6] = [0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+const BZIP2_MAGIC: [u8; 3] = [0x42, 0x5a, 0x68];
+
+/// Decompresses `bytes` if they start with a recognized magic number, passing them through
+/// unchanged otherwise.
+pub(crate) fn maybe_decompress(bytes: Vec<u8>) -> Result<Vec<u8>, AssuoError> {
+    if bytes.starts_with(&GZIP_MAGIC) {
+        decompress_with(flate2::read::GzDecoder::new(&bytes[..]))
+    } else if bytes.starts_with(&ZSTD_MAGIC) {
+        zstd::stream::decode_all(&bytes[..])
+            .map_err(|e| AssuoError::Decompress(format!("failed to decompress zstd source: {}", e)))
+    } else if bytes.starts_with(&XZ_MAGIC) {
+        decompress_with(xz2::read::XzDecoder::new(&bytes[..]))
+    } else if bytes.starts_with(&BZIP2_MAGIC) {
+        decompress_with(bzip2::read::BzDecoder::new(&bytes[..]))
+    } else {
+        Ok(bytes)
+    }
+}
+
+fn decompress_with<R: Read>(mut decoder: R) -> Result<Vec<u8>, AssuoError> {
+    let mut out = Vec::new();
+    decoder
+        .read_to_end(&mut out)
+        .map_err(|e| AssuoError::Decompress(format!("failed to decompress source: {}", e)))?;
+    Ok(out)
+}
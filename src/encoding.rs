@@ -0,0 +1,12 @@
+//! Shared base64/hex decoding for sources that carry binary data as a TOML string -- encryption
+//! keys/nonces, and `bytes` sources that'd rather not spell out every byte in an array.
+
+use base64::Engine;
+
+pub(crate) fn from_base64(value: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    base64::engine::general_purpose::STANDARD.decode(value)
+}
+
+pub(crate) fn from_hex(value: &str) -> Result<Vec<u8>, hex::FromHexError> {
+    hex::decode(value)
+}
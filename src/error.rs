@@ -0,0 +1,65 @@
+//! Error types surfaced while resolving an [`AssuoSource`](crate::models::AssuoSource).
+
+use std::fmt;
+
+/// Anything that can go wrong while turning a source into bytes.
+#[derive(Debug)]
+pub enum AssuoError {
+    /// Fetching a `url`/`assuo-url` source over HTTP failed.
+    Http(reqwest::Error),
+    /// Reading a `file`/`assuo-file` source from disk failed.
+    Io(std::io::Error),
+    /// Parsing a nested Assuo patch file (`assuo-url`/`assuo-file`) as TOML failed.
+    Toml(toml::de::Error),
+    /// A fetched `assuo-url`/`assuo-file` source wasn't valid UTF-8.
+    Utf8(std::string::FromUtf8Error),
+    /// Decrypting an `encrypted` source failed, whether from a malformed key/nonce or a failed
+    /// authentication tag check.
+    Crypto(String),
+    /// Resolving an `assuo-url`/`assuo-file` source hit a cycle or exceeded the max recursion
+    /// depth.
+    Recursion(String),
+    /// Decompressing a `url`/`file` source failed, whether from truncated/corrupted bytes or a
+    /// payload whose magic bytes matched a format it isn't actually valid for.
+    Decompress(String),
+}
+
+impl fmt::Display for AssuoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssuoError::Http(e) => write!(f, "failed to fetch source over http: {}", e),
+            AssuoError::Io(e) => write!(f, "failed to read source from disk: {}", e),
+            AssuoError::Toml(e) => write!(f, "failed to parse nested assuo patch file: {}", e),
+            AssuoError::Utf8(e) => write!(f, "nested assuo patch file wasn't valid utf-8: {}", e),
+            AssuoError::Crypto(message) => write!(f, "failed to decrypt source: {}", message),
+            AssuoError::Recursion(message) => write!(f, "{}", message),
+            AssuoError::Decompress(message) => write!(f, "failed to decompress source: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AssuoError {}
+
+impl From<reqwest::Error> for AssuoError {
+    fn from(e: reqwest::Error) -> Self {
+        AssuoError::Http(e)
+    }
+}
+
+impl From<std::io::Error> for AssuoError {
+    fn from(e: std::io::Error) -> Self {
+        AssuoError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for AssuoError {
+    fn from(e: toml::de::Error) -> Self {
+        AssuoError::Toml(e)
+    }
+}
+
+impl From<std::string::FromUtf8Error> for AssuoError {
+    fn from(e: std::string::FromUtf8Error) -> Self {
+        AssuoError::Utf8(e)
+    }
+}
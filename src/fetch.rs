@@ -0,0 +1,43 @@
+//! Turns a `url`/`file` source into raw bytes.
+
+use crate::cache;
+use crate::decompress::maybe_decompress;
+use crate::error::AssuoError;
+
+/// Fetches the bytes at `url` over HTTP, transparently decompressing them unless `decompress`
+/// is `false`. Short-circuits through the content-addressed cache when it has a hit, and fills
+/// it in on a miss so the next run doesn't need the network at all.
+pub(crate) async fn fetch_url(url: &str, decompress: bool) -> Result<Vec<u8>, AssuoError> {
+    let key = cache::index_key(url, decompress);
+
+    if cache::enabled() {
+        if let Some(cached) = cache::get(&key).await {
+            return Ok(cached);
+        }
+    }
+
+    let bytes = reqwest::get(url).await?.bytes().await?.to_vec();
+    let bytes = if decompress {
+        maybe_decompress(bytes)?
+    } else {
+        bytes
+    };
+
+    if cache::enabled() {
+        // a cache write failing (e.g. unwritable cache dir) shouldn't fail the fetch itself
+        let _ = cache::put(&key, &bytes).await;
+    }
+
+    Ok(bytes)
+}
+
+/// Reads the bytes of the file at `path` from disk, transparently decompressing them unless
+/// `decompress` is `false`.
+pub(crate) async fn fetch_file(path: &str, decompress: bool) -> Result<Vec<u8>, AssuoError> {
+    let bytes = tokio::fs::read(path).await?;
+    if decompress {
+        maybe_decompress(bytes)
+    } else {
+        Ok(bytes)
+    }
+}
@@ -1,19 +1,29 @@
 use std::io::prelude::*;
 
+pub mod cache;
+pub mod crypto;
+pub mod decompress;
+pub mod encoding;
+pub mod error;
+pub mod fetch;
 pub mod models;
+pub mod patch;
+pub mod recursion;
 use models::*;
 
 fn help() {
     eprintln!(
         "OVERVIEW: assuo patch maker
 
-USAGE: assuo [--url source_url]/[--file file_location]/[--init]/[--help]
+USAGE: assuo [--url source_url]/[--file file_location]/[--init]/[--help]/[--no-cache]/[--max-depth n]
 
 OPTIONS:
---url    Loads an assuo patch file from the internet.
---file   Loads an assuo patch file from disk.
---init   Makes a new blank assuo patch file.
---help   Prints help.
+--url          Loads an assuo patch file from the internet.
+--file         Loads an assuo patch file from disk.
+--init         Makes a new blank assuo patch file.
+--no-cache     Skips the content-addressed cache, always re-fetching url/assuo-url sources.
+--max-depth    Sets how many assuo-url/assuo-file includes can nest before giving up. Defaults to 32.
+--help         Prints help.
 "
     );
 }
@@ -45,90 +55,13 @@ fn init(file_name: Option<String>) {
     }
 }
 
-fn do_patch(file: AssuoFile) -> Vec<u8> {
-    // in the future, it would be nice to be able to apply patches as they come along so that everything is
-    // non-blocking and fast, but for now, it's much simpler to "resolve everything -> apply patches"
-
-    // resolve the base
-    let mut file = file.resolve();
-
-    // resolve every patch
-    let patches = file
-        .patch
-        .unwrap_or_default()
-        .into_iter()
-        .map(|p| p.resolve())
-        .collect::<Vec<_>>();
-
-    // so right now i'm just going for simplicity rather than speed, so i just need a method that works for these patches
-    // one ideal thing to do is to maintain another Vec with a Vec of indexes that is in the original file
-    // really bad in terms of performance, *but* it is simple for finding the index something should be at
-
-    let mut indexes = Vec::with_capacity(file.source.len());
-    for i in 0..file.source.len() {
-        indexes.push(vec![i]);
-    }
-
-    fn get_index(indexes: &Vec<Vec<usize>>, i: usize) -> usize {
-        for (idx, index) in indexes.iter().enumerate() {
-            if index.contains(&i) {
-                return idx;
-            }
-        }
-
-        panic!("assuo patch out of bounds?");
-    }
-
-    // now, we apply each patch sequentially, maintaining the indexes vec as we go
-    for patch in patches {
-        match patch {
-            AssuoPatch::Insert { way, spot, source } => {
-                let insertion_point = get_index(&indexes, spot);
-
-                let insertion_point = match way {
-                    Direction::Pre => insertion_point,
-                    Direction::Post => insertion_point + 1,
-                };
-
-                indexes.splice(
-                    insertion_point..insertion_point,
-                    (0..source.len()).map(|_| vec![std::usize::MAX]),
-                );
-
-                file.source.splice(insertion_point..insertion_point, source);
-            }
-            AssuoPatch::Remove { way, spot, count } => {
-                let insertion_point = get_index(&indexes, spot);
-
-                let insertion_point = match way {
-                    Direction::Post => insertion_point + 1,
-                    Direction::Pre => insertion_point - count,
-                };
-
-                let fold = indexes[insertion_point..(insertion_point + count)]
-                    .iter()
-                    .fold(Vec::new(), |mut acc, elem| {
-                        for element in elem {
-                            if !acc.contains(element) {
-                                acc.push(*element);
-                            }
-                        }
-                        acc
-                    });
-
-                indexes.splice(insertion_point..(insertion_point + count), vec![fold]);
-
-                file.source
-                    .splice(insertion_point..(insertion_point + count), vec![]);
-            }
-        }
-    }
-
-    file.source
-}
-
 #[paw::main]
 fn main(args: paw::Args) {
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start the tokio runtime");
+    runtime.block_on(run(args));
+}
+
+async fn run(args: paw::Args) {
     // ARGUMENT PARSING:
     // assuo aims to "do one thing, and do it right". our arg parsing aims to capture the unix philosophy by giving a
     // similar experience to what tools like `cat` offer.
@@ -179,6 +112,7 @@ fn main(args: paw::Args) {
 
     let mut got_arg = false;
     let mut do_init = false;
+    let mut awaiting_max_depth = false;
 
     for arg in args.skip(1) {
         got_arg = true;
@@ -187,6 +121,13 @@ fn main(args: paw::Args) {
             return;
         }
 
+        if awaiting_max_depth {
+            let max_depth = arg.parse().expect("--max-depth expects an integer");
+            recursion::set_max_depth(max_depth);
+            awaiting_max_depth = false;
+            continue;
+        }
+
         let trim_for_arg = if arg.starts_with("--") {
             2
         } else if arg.starts_with("-") {
@@ -205,11 +146,17 @@ fn main(args: paw::Args) {
                 return;
             } else if arg == "i" || arg == "init" {
                 do_init = true;
+            } else if arg == "no-cache" {
+                cache::set_no_cache(true);
+            } else if arg == "max-depth" {
+                awaiting_max_depth = true;
             }
         } else {
             let config =
                 toml::from_str::<AssuoFile>(&std::fs::read_to_string(arg).unwrap()).unwrap();
-            do_patch(config);
+            patch::do_patch(config, recursion::ResolveContext::default())
+                .await
+                .expect("failed to resolve and apply patch");
             return;
         }
     }
@@ -219,6 +166,10 @@ fn main(args: paw::Args) {
         return;
     }
 
+    if awaiting_max_depth {
+        panic!("--max-depth expects an integer");
+    }
+
     // if we didn't get anything, try to read from an assuo.toml file to print that out
     let assuo_config = match std::fs::read_to_string("assuo.toml") {
         Ok(assuo_config) => assuo_config,
@@ -232,9 +183,11 @@ fn main(args: paw::Args) {
 
     // TODO: display help if no "assuo.toml" found (and print that no assuo.toml was found, showing help)
     let config = toml::from_str::<AssuoFile>(&assuo_config).unwrap();
-    let patch = do_patch(config);
+    let patched = patch::do_patch(config, recursion::ResolveContext::default())
+        .await
+        .expect("failed to resolve and apply patch");
     std::io::stdout()
         .lock()
-        .write_all(&patch)
+        .write_all(&patched)
         .expect("to print to stdout");
 }
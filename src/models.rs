@@ -1,9 +1,64 @@
 //! This module holds the data structures used when deserializing an Assuo patch file.
 
+use futures::future::{BoxFuture, FutureExt};
 use serde::de::Error;
 use serde::Deserialize;
 use toml::Value;
 
+use crate::crypto;
+use crate::encoding::{from_base64, from_hex};
+use crate::error::AssuoError;
+use crate::fetch::{fetch_file, fetch_url};
+use crate::recursion::ResolveContext;
+
+/// A `url`/`file` source, plus the knobs that control how its bytes get post-processed.
+#[derive(Debug)]
+pub struct RemoteSource {
+    /// The URL to fetch, or the path on disk to read.
+    pub location: String,
+    /// Whether to auto-detect and transparently decompress the fetched bytes based on their
+    /// leading magic bytes. Defaults to `true`; set `decompress = false` in the TOML to get the
+    /// raw bytes back verbatim.
+    pub decompress: bool,
+}
+
+/// An `encrypted` source: ciphertext (from any other source) plus the key/nonce needed to
+/// decrypt it with ChaCha20-Poly1305.
+#[derive(Debug)]
+pub struct EncryptedSource {
+    /// The source that resolves to the ciphertext, trailing authentication tag included.
+    pub source: Box<AssuoSource>,
+    /// Where to find the 256-bit decryption key.
+    pub key: KeySource,
+    /// The 96-bit nonce the ciphertext was encrypted with.
+    pub nonce: Vec<u8>,
+}
+
+/// Where an `encrypted` source's key comes from.
+#[derive(Debug)]
+pub enum KeySource {
+    /// A base64-encoded key given directly in the TOML (via `key = "..."`).
+    Inline(Vec<u8>),
+    /// A base64-encoded key read from an environment variable (via `key-env = "VAR_NAME"`), so
+    /// the secret itself doesn't have to land in the patch file.
+    Env(String),
+}
+
+impl KeySource {
+    fn resolve(&self) -> Result<Vec<u8>, AssuoError> {
+        match self {
+            KeySource::Inline(key) => Ok(key.clone()),
+            KeySource::Env(var) => {
+                let value = std::env::var(var).map_err(|_| {
+                    AssuoError::Crypto(format!("env var '{}' isn't set", var))
+                })?;
+                from_base64(&value)
+                    .map_err(|e| AssuoError::Crypto(format!("env var '{}' wasn't base64: {}", var, e)))
+            }
+        }
+    }
+}
+
 /// Represents an Assuo patch file. Every Assuo patch file has a primary source that it is based off of,
 /// and a series of patches that it needs to apply to the source.
 #[derive(Debug, Deserialize)]
@@ -29,15 +84,17 @@ pub enum AssuoSource {
     /// Some text. Plain and simple.
     Text(String),
     /// Fetches data at a given URL, and will use the payload to inject it.
-    Url(String),
+    Url(RemoteSource),
     /// Reads a file on disk at the given path, and will read the file to inject it.
-    File(String),
+    File(RemoteSource),
     /// Reads an Assuo patch file from the URL specified, and after applying that Assuo patch file, uses the resultant
     /// data as part of the modification.
     AssuoUrl(String),
     /// Reads an Assuo patch file from disk, and after applying that Assuo patch file, uses the resultant data as part
     /// of the modification.
     AssuoFile(String),
+    /// Decrypts a ChaCha20-Poly1305-encrypted payload (from any other source) before injecting it.
+    Encrypted(EncryptedSource),
 }
 
 /// Represents a single action of patching.
@@ -72,38 +129,48 @@ pub enum Direction {
 
 // some mildly ugly stuff
 
-impl AssuoFile {
-    pub fn resolve(self) -> AssuoFile<Vec<u8>> {
-        let source = self.source.resolve();
-        AssuoFile {
-            source,
-            patch: self.patch,
-        }
-    }
-}
-
-impl AssuoPatch {
-    pub fn resolve(self) -> AssuoPatch<Vec<u8>> {
-        match self {
-            AssuoPatch::Insert { way, spot, source } => {
-                let source = source.resolve();
-                AssuoPatch::<Vec<u8>>::Insert { way, spot, source }
-            }
-            AssuoPatch::Remove { way, spot, count } => {
-                AssuoPatch::<Vec<u8>>::Remove { way, spot, count }
+impl AssuoSource {
+    /// Resolves this source down to its raw bytes, fetching over the network/disk as needed.
+    /// `ctx` tracks the chain of `assuo-url`/`assuo-file` includes taken to get here, so a loop
+    /// aborts cleanly instead of recursing forever.
+    ///
+    /// Boxed because `AssuoUrl`/`AssuoFile` recurse back into [`crate::patch::do_patch`], which
+    /// in turn resolves more sources -- an `async fn` can't otherwise refer to itself.
+    pub fn resolve(self, ctx: ResolveContext) -> BoxFuture<'static, Result<Vec<u8>, AssuoError>> {
+        async move {
+            match self {
+                AssuoSource::Bytes(bytes) => Ok(bytes),
+                AssuoSource::Text(text) => Ok(text.into_bytes()),
+                AssuoSource::Url(source) => fetch_url(&source.location, source.decompress).await,
+                AssuoSource::File(source) => {
+                    fetch_file(&source.location, source.decompress).await
+                }
+                AssuoSource::AssuoUrl(url) => {
+                    let ctx = ctx.enter(url.clone())?;
+                    resolve_nested(fetch_url(&url, false).await?, ctx).await
+                }
+                AssuoSource::AssuoFile(path) => {
+                    let canonical = tokio::fs::canonicalize(&path).await?;
+                    let ctx = ctx.enter(canonical.to_string_lossy().into_owned())?;
+                    resolve_nested(fetch_file(&path, false).await?, ctx).await
+                }
+                AssuoSource::Encrypted(encrypted) => {
+                    let ciphertext = encrypted.source.resolve(ctx).await?;
+                    let key = encrypted.key.resolve()?;
+                    crypto::decrypt(&key, &encrypted.nonce, ciphertext)
+                }
             }
         }
+        .boxed()
     }
 }
 
-impl AssuoSource {
-    pub fn resolve(self) -> Vec<u8> {
-        match self {
-            AssuoSource::Bytes(bytes) => bytes,
-            AssuoSource::Text(text) => text.into_bytes(),
-            _ => panic!("unimplemented route"),
-        }
-    }
+/// Parses `bytes` as a nested Assuo patch file and runs it to completion, so the result can be
+/// spliced in as though it were any other source.
+async fn resolve_nested(bytes: Vec<u8>, ctx: ResolveContext) -> Result<Vec<u8>, AssuoError> {
+    let text = String::from_utf8(bytes)?;
+    let nested = toml::from_str::<AssuoFile>(&text)?;
+    crate::patch::do_patch(nested, ctx).await
 }
 
 // == ugly serialization stuff below ==
@@ -215,6 +282,47 @@ impl<'de> Deserialize<'de> for AssuoSource {
     }
 }
 
+/// Parses the table inside `encrypted = { source = ..., key/key-env = ..., nonce = ... }`.
+fn parse_encrypted<'de, D>(mut table: toml::value::Table) -> Result<EncryptedSource, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let source = match table.remove("source") {
+        Some(value) => AssuoSource::deserialize_toml::<D>(value)?,
+        None => return Err(serde::de::Error::custom("'encrypted' is missing 'source'")),
+    };
+
+    let key = match (table.remove("key"), table.remove("key-env")) {
+        (Some(toml::Value::String(key)), None) => KeySource::Inline(from_base64(&key).map_err(
+            |e| serde::de::Error::custom(format!("'encrypted.key' wasn't base64: {}", e)),
+        )?),
+        (None, Some(toml::Value::String(var))) => KeySource::Env(var),
+        (Some(_), Some(_)) => {
+            return Err(serde::de::Error::custom(
+                "'encrypted' can't have both 'key' and 'key-env'",
+            ))
+        }
+        _ => {
+            return Err(serde::de::Error::custom(
+                "'encrypted' is missing 'key' (or 'key-env')",
+            ))
+        }
+    };
+
+    let nonce = match table.remove("nonce") {
+        Some(toml::Value::String(nonce)) => from_base64(&nonce).map_err(|e| {
+            serde::de::Error::custom(format!("'encrypted.nonce' wasn't base64: {}", e))
+        })?,
+        _ => return Err(serde::de::Error::custom("'encrypted' is missing 'nonce'")),
+    };
+
+    Ok(EncryptedSource {
+        source: Box::new(source),
+        key,
+        nonce,
+    })
+}
+
 impl<'de> TomlDeserialize<'de> for AssuoSource {
     fn deserialize_toml<D>(value: Value) -> Result<Self, D::Error>
     where
@@ -222,7 +330,17 @@ impl<'de> TomlDeserialize<'de> for AssuoSource {
     {
         // TODO: this is hideous but it works and it's good enough, so... :yum:
         match value {
-            toml::Value::Table(table) => {
+            toml::Value::Table(mut table) => {
+                // `url`/`file` are the only sources that take an extra knob (`decompress`), so
+                // peel that off first and then fall back to the usual "exactly one key" shape.
+                let decompress = match table.remove("decompress") {
+                    Some(toml::Value::Boolean(decompress)) => decompress,
+                    Some(_) => {
+                        return Err(serde::de::Error::custom("'decompress' wasn't a bool"))
+                    }
+                    None => true,
+                };
+
                 if table.len() != 1 {
                     Err(serde::de::Error::custom("more than 1"))
                 } else {
@@ -253,14 +371,31 @@ impl<'de> TomlDeserialize<'de> for AssuoSource {
                         }
                         toml::Value::String(string) => match name.as_str() {
                             "text" => Ok(AssuoSource::Text(string)),
-                            "url" => Ok(AssuoSource::Url(string)),
-                            "file" => Ok(AssuoSource::File(string)),
+                            "url" => Ok(AssuoSource::Url(RemoteSource {
+                                location: string,
+                                decompress,
+                            })),
+                            "file" => Ok(AssuoSource::File(RemoteSource {
+                                location: string,
+                                decompress,
+                            })),
                             "assuo-url" => Ok(AssuoSource::AssuoUrl(string)),
                             "assuo-file" => Ok(AssuoSource::AssuoFile(string)),
+                            "bytes-base64" => Ok(AssuoSource::Bytes(
+                                from_base64(&string).map_err(|e| {
+                                    serde::de::Error::custom(format!("invalid base64: {}", e))
+                                })?,
+                            )),
+                            "bytes-hex" => Ok(AssuoSource::Bytes(from_hex(&string).map_err(
+                                |e| serde::de::Error::custom(format!("invalid hex: {}", e)),
+                            )?)),
                             _ => Err(serde::de::Error::custom(
-                                "didn't get key text/url/file/assuo-url/assuo-file",
+                                "didn't get key text/url/file/assuo-url/assuo-file/bytes-base64/bytes-hex",
                             )),
                         },
+                        toml::Value::Table(inner) if name == "encrypted" => {
+                            Ok(AssuoSource::Encrypted(parse_encrypted::<D>(inner)?))
+                        }
                         _ => Err(serde::de::Error::custom("invalid value")),
                     }
                 }
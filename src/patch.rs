@@ -0,0 +1,138 @@
+//! Drives resolving an [`AssuoFile`] and applying its patches.
+
+use futures::future::try_join_all;
+
+use crate::error::AssuoError;
+use crate::models::{AssuoFile, AssuoPatch, Direction};
+use crate::recursion::ResolveContext;
+
+/// Resolves the base source and every patch's source concurrently -- none of them depend on
+/// one another -- and hands back plain bytes for the sequential splice loop in [`do_patch`] to
+/// work with. Every source resolves starting from the same `ctx`, since they're independent
+/// branches of the same include chain rather than a continuation of one another.
+async fn resolve_all(
+    file: AssuoFile,
+    ctx: ResolveContext,
+) -> Result<(Vec<u8>, Vec<AssuoPatch<Vec<u8>>>), AssuoError> {
+    let AssuoFile { source, patch } = file;
+    let patches = patch.unwrap_or_default();
+
+    // `Remove` patches don't carry a source, so split the patches into a `()`-shaped skeleton
+    // (to put things back in order afterwards) and a flat list of the `Insert` sources that
+    // actually need fetching.
+    let mut insert_sources = Vec::new();
+    let mut skeleton = Vec::with_capacity(patches.len());
+
+    for patch in patches {
+        match patch {
+            AssuoPatch::Insert { way, spot, source } => {
+                insert_sources.push(source.resolve(ctx.clone()));
+                skeleton.push(AssuoPatch::Insert {
+                    way,
+                    spot,
+                    source: (),
+                });
+            }
+            AssuoPatch::Remove { way, spot, count } => {
+                skeleton.push(AssuoPatch::Remove { way, spot, count });
+            }
+        }
+    }
+
+    let (source, resolved_inserts) =
+        futures::try_join!(source.resolve(ctx), try_join_all(insert_sources))?;
+
+    let mut resolved_inserts = resolved_inserts.into_iter();
+    let patches = skeleton
+        .into_iter()
+        .map(|patch| match patch {
+            AssuoPatch::Insert { way, spot, .. } => AssuoPatch::Insert {
+                way,
+                spot,
+                source: resolved_inserts
+                    .next()
+                    .expect("one resolved source per insert patch"),
+            },
+            AssuoPatch::Remove { way, spot, count } => AssuoPatch::Remove { way, spot, count },
+        })
+        .collect();
+
+    Ok((source, patches))
+}
+
+pub async fn do_patch(file: AssuoFile, ctx: ResolveContext) -> Result<Vec<u8>, AssuoError> {
+    // in the future, it would be nice to be able to apply patches as they come along so that everything is
+    // non-blocking and fast, but for now, resolving everything concurrently up front and then applying
+    // patches sequentially is simple and already gets us the "non-blocking and fast" part for the fetches
+
+    let (mut source, patches) = resolve_all(file, ctx).await?;
+
+    // so right now i'm just going for simplicity rather than speed, so i just need a method that works for these patches
+    // one ideal thing to do is to maintain another Vec with a Vec of indexes that is in the original file
+    // really bad in terms of performance, *but* it is simple for finding the index something should be at
+
+    let mut indexes = Vec::with_capacity(source.len());
+    for i in 0..source.len() {
+        indexes.push(vec![i]);
+    }
+
+    fn get_index(indexes: &Vec<Vec<usize>>, i: usize) -> usize {
+        for (idx, index) in indexes.iter().enumerate() {
+            if index.contains(&i) {
+                return idx;
+            }
+        }
+
+        panic!("assuo patch out of bounds?");
+    }
+
+    // now, we apply each patch sequentially, maintaining the indexes vec as we go
+    for patch in patches {
+        match patch {
+            AssuoPatch::Insert {
+                way,
+                spot,
+                source: inserted,
+            } => {
+                let insertion_point = get_index(&indexes, spot);
+
+                let insertion_point = match way {
+                    Direction::Pre => insertion_point,
+                    Direction::Post => insertion_point + 1,
+                };
+
+                indexes.splice(
+                    insertion_point..insertion_point,
+                    (0..inserted.len()).map(|_| vec![std::usize::MAX]),
+                );
+
+                source.splice(insertion_point..insertion_point, inserted);
+            }
+            AssuoPatch::Remove { way, spot, count } => {
+                let insertion_point = get_index(&indexes, spot);
+
+                let insertion_point = match way {
+                    Direction::Post => insertion_point + 1,
+                    Direction::Pre => insertion_point - count,
+                };
+
+                let fold = indexes[insertion_point..(insertion_point + count)]
+                    .iter()
+                    .fold(Vec::new(), |mut acc, elem| {
+                        for element in elem {
+                            if !acc.contains(element) {
+                                acc.push(*element);
+                            }
+                        }
+                        acc
+                    });
+
+                indexes.splice(insertion_point..(insertion_point + count), vec![fold]);
+
+                source.splice(insertion_point..(insertion_point + count), vec![]);
+            }
+        }
+    }
+
+    Ok(source)
+}
@@ -0,0 +1,56 @@
+//! Recursion bookkeeping for `assuo-url`/`assuo-file` sources, which run a whole nested patch
+//! file and can recursively point back at each other.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::AssuoError;
+
+const DEFAULT_MAX_DEPTH: usize = 32;
+
+static MAX_DEPTH: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_DEPTH);
+
+/// Sets the maximum recursion depth, driven by the `--max-depth` CLI flag.
+pub(crate) fn set_max_depth(max_depth: usize) {
+    MAX_DEPTH.store(max_depth, Ordering::Relaxed);
+}
+
+fn max_depth() -> usize {
+    MAX_DEPTH.load(Ordering::Relaxed)
+}
+
+/// Tracks the chain of `assuo-url`/`assuo-file` includes currently being resolved, so a loop
+/// (file A includes B includes A) aborts with a clear error instead of recursing forever.
+#[derive(Clone, Debug, Default)]
+pub struct ResolveContext {
+    visited: HashSet<String>,
+    depth: usize,
+}
+
+impl ResolveContext {
+    /// Records that `canonical` is about to be entered, returning a context for resolving it --
+    /// or an error if doing so would revisit something already on this chain or exceed the max
+    /// recursion depth.
+    pub(crate) fn enter(&self, canonical: String) -> Result<ResolveContext, AssuoError> {
+        if self.depth >= max_depth() {
+            return Err(AssuoError::Recursion(format!(
+                "exceeded max assuo include recursion depth of {}",
+                max_depth()
+            )));
+        }
+
+        if self.visited.contains(&canonical) {
+            return Err(AssuoError::Recursion(format!(
+                "cyclic assuo include: {}",
+                canonical
+            )));
+        }
+
+        let mut visited = self.visited.clone();
+        visited.insert(canonical);
+        Ok(ResolveContext {
+            visited,
+            depth: self.depth + 1,
+        })
+    }
+}